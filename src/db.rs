@@ -0,0 +1,447 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{params, Connection, DatabaseName};
+
+use crate::dictionary::{Definition, DictionaryEntry, WiktionaryClient};
+use crate::fuzzy::FuzzyWordIndex;
+use crate::language::{self, Language};
+use crate::migrations;
+use crate::{super_memo_2, MaturityWeights, SuperMemoItem};
+
+// The async-facing surface of the SRS. `KnowledgeDB` is a fine fit for the CLI, which only
+// ever has one operation in flight at a time, but a GUI or web frontend needs to be able to
+// have several requests in progress against the same database concurrently rather than
+// serialising everything behind shelling out to the binary. Every method here mirrors one
+// of `KnowledgeDB`'s operations one-for-one.
+#[async_trait]
+pub trait Database: Send + Sync {
+    async fn add_sentence(&self, language_code: String, sentence: String);
+    async fn get_word_to_review(&self) -> Option<String>;
+    async fn review_word(&self, word: String, response_quality: f32);
+    async fn get_sentence_to_review(&self, word: String) -> Option<String>;
+    async fn get_sentences_for_word(&self, word: String) -> Vec<String>;
+    async fn fuzzy_find_words(&self, query: String, prefix: bool) -> Vec<String>;
+    async fn get_definitions(&self, word: String) -> Vec<Definition>;
+}
+
+// A `Database` backed by a pool of SQLite connections (`r2d2`/`r2d2_sqlite`) instead of the
+// single blocking `Connection` the CLI uses. Every method borrows a connection from the
+// pool only for the span of a `spawn_blocking` task, so many requests - e.g. several
+// clients hitting the HTTP API in `server::serve` at once - can run against the same
+// database concurrently instead of queuing on one handle.
+pub struct PooledDatabase {
+    pool: Pool<SqliteConnectionManager>,
+    languages: Arc<HashMap<String, Arc<dyn Language>>>,
+    dictionary: Arc<WiktionaryClient>,
+    maturity_weights: Arc<MaturityWeights>,
+    fuzzy_index: Arc<Mutex<Option<FuzzyWordIndex>>>,
+}
+
+impl PooledDatabase {
+    pub fn open(db_path: &str) -> Self {
+        let manager = SqliteConnectionManager::file(db_path)
+            .with_init(|conn| {
+                conn.pragma_update(Some(DatabaseName::Main), "foreign_keys", true)?;
+                // WAL lets readers and writers run concurrently instead of blocking each
+                // other, and `busy_timeout` makes a writer that does lose the race wait
+                // for the lock instead of every `.unwrap()`'d call panicking on
+                // `SQLITE_BUSY` the moment two pooled connections write at once.
+                conn.pragma_update(Some(DatabaseName::Main), "journal_mode", "WAL")?;
+                conn.busy_timeout(std::time::Duration::from_secs(5))
+            });
+        let pool = Pool::new(manager).unwrap();
+
+        // Bring the schema up to date once, up front, rather than racing every pooled
+        // connection through the migration check.
+        let mut conn = pool.get().unwrap();
+        migrations::migrations().to_latest(&mut conn).unwrap();
+        drop(conn);
+
+        Self {
+            pool,
+            languages: Arc::new(language::registry()),
+            dictionary: Arc::new(WiktionaryClient::new()),
+            maturity_weights: Arc::new(MaturityWeights::default()),
+            fuzzy_index: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+// Not language-scoped: see the migration #3 comment in migrations.rs. Harmless while only
+// Japanese is registered.
+fn word_frequency(conn: &Connection, word: &str) -> i64 {
+    conn.query_row(
+        "SELECT frequency FROM words WHERE text = ?", [word], |row| row.get(0)
+    ).unwrap_or(i64::MAX)
+}
+
+// Not language-scoped: see the migration #3 comment in migrations.rs. Harmless while only
+// Japanese is registered.
+fn fold_surface_form(conn: &Connection, surface: &str) -> Option<String> {
+    conn.query_row(
+        "SELECT words.text
+        FROM word_forms
+            INNER JOIN words ON words.id = word_forms.word_id
+        WHERE word_forms.surface_form = ?", [surface], |row| row.get(0)
+    ).ok()
+}
+
+// Fetches Wiktionary's definitions and inflected forms for any of `words` we haven't
+// already successfully looked up. Deliberately done outside any write transaction: one
+// blocking HTTP round-trip per new word would otherwise hold the SQLite write lock and
+// stall every other writer for as long as the import takes.
+fn fetch_new_dictionary_entries(conn: &Connection, dictionary: &WiktionaryClient, words: &[String]) -> HashMap<String, DictionaryEntry> {
+    let mut entries = HashMap::new();
+    for word in words {
+        if entries.contains_key(word) {
+            continue;
+        }
+
+        let already_fetched: bool = conn.query_row(
+            "SELECT dictionary_fetched_at IS NOT NULL FROM words WHERE text = ?", [word], |row| row.get(0)
+        ).unwrap_or(false);
+
+        if already_fetched {
+            continue;
+        }
+
+        // A request failure (`Err`) isn't inserted at all, so `dictionary_fetched_at` never
+        // gets stamped for it and the word is retried on the next import instead of
+        // permanently caching "no definition" for a transient network error.
+        if let Ok(entry) = dictionary.fetch(word) {
+            entries.insert(word.clone(), entry.unwrap_or_default());
+        }
+    }
+    entries
+}
+
+// Writes an already-fetched dictionary `entry` for `word_id` and stamps
+// `dictionary_fetched_at`. Pure DB writes, safe to call from inside a transaction.
+//
+// Re-checks `dictionary_fetched_at` itself rather than trusting the caller, since the fetch
+// happened outside the transaction: two `add_sentence` calls racing on the same new word
+// could otherwise both insert the same definitions/inflected forms twice.
+fn store_dictionary_entry(tx: &rusqlite::Transaction, word_id: i64, entry: &DictionaryEntry) {
+    let already_fetched: bool = tx.query_row(
+        "SELECT dictionary_fetched_at IS NOT NULL FROM words WHERE id = ?", [word_id], |row| row.get(0)
+    ).unwrap_or(true);
+
+    if already_fetched {
+        return;
+    }
+
+    for definition in &entry.definitions {
+        tx.execute(
+            "INSERT INTO definitions(word_id, part_of_speech, gloss)
+            VALUES (?, ?, ?)", params![word_id, definition.part_of_speech, definition.gloss]
+        ).unwrap();
+    }
+
+    for form in &entry.inflected_forms {
+        tx.execute(
+            "INSERT OR IGNORE INTO word_forms(word_id, surface_form)
+            VALUES (?, ?)", params![word_id, form]
+        ).unwrap();
+    }
+
+    tx.execute(
+        "UPDATE words SET dictionary_fetched_at = ? WHERE id = ?",
+        params![format!("{}", chrono::Utc::now()), word_id]
+    ).unwrap();
+}
+
+#[async_trait]
+impl Database for PooledDatabase {
+    async fn add_sentence(&self, language_code: String, sentence: String) {
+        let pool = self.pool.clone();
+        let languages = self.languages.clone();
+        let dictionary = self.dictionary.clone();
+        let fuzzy_index = self.fuzzy_index.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let Some(language) = languages.get(language_code.as_str()) else {
+                println!("{}: no such language registered.", language_code);
+                return;
+            };
+
+            let mut conn = pool.get().unwrap();
+
+            // Nothing to do if we've already imported this exact sentence - bail out
+            // before tokenizing or doing any per-word dictionary-fetch-status lookups.
+            let already_imported: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sentences WHERE text = ?)", [sentence.as_str()], |row| row.get(0)
+            ).unwrap_or(false);
+            if already_imported {
+                return;
+            }
+
+            let words = language.tokenize_to_base_forms(
+                sentence.as_str(), &|surface| fold_surface_form(&conn, surface));
+
+            // Fetch dictionary entries for any new words before opening the write
+            // transaction below, so the network round-trips don't hold the SQLite write lock.
+            let mut dictionary_entries = fetch_new_dictionary_entries(&conn, &dictionary, &words);
+
+            let tx = conn.transaction().unwrap();
+            if tx.execute(
+                "INSERT OR IGNORE INTO sentences(text, language)
+                    VALUES(?, ?);", params![sentence.as_str(), language.code()]).unwrap() == 1 {
+
+                let sentence_id = tx.last_insert_rowid();
+
+                for word in &words {
+                    let frequency = language.word_freq(word);
+
+                    tx.execute(
+                        "INSERT INTO words(count, frequency, text, language)
+                        VALUES(1, ?, ?, ?)
+                        ON CONFLICT(text) DO UPDATE SET count=count + 1", params!(frequency, word, language.code())).unwrap();
+
+                    let word_id: i64 = tx.query_row(
+                        "SELECT id, text
+                        FROM words
+                            WHERE text = ?", [word], |row| row.get(0)
+                    ).unwrap();
+
+                    tx.execute(
+                        "INSERT OR IGNORE INTO word_sentence(word_id, sentence_id)
+                        VALUES(?, ?);", params![word_id, sentence_id]).unwrap();
+
+                    // `remove` so a word repeated later in the same sentence doesn't get
+                    // its definitions/inflected forms inserted more than once.
+                    if let Some(entry) = dictionary_entries.remove(word) {
+                        store_dictionary_entry(&tx, word_id, &entry);
+                    }
+                }
+            }
+            tx.commit().unwrap();
+
+            *fuzzy_index.lock().unwrap() = None;
+        }).await.unwrap();
+    }
+
+    async fn get_word_to_review(&self) -> Option<String> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            let now_time = format!("{}", chrono::Utc::now());
+            if let Ok(word) = conn.query_row(
+                "SELECT repitition, next_review_at, text FROM words
+                WHERE reviewed = TRUE
+                    AND next_review_at < ?
+                ORDER BY next_review_at ASC
+                LIMIT 1", params!(now_time),
+                |row| row.get(2)
+            ) {
+                return Some(word);
+            }
+
+            conn.query_row("
+                SELECT text, frequency, reviewed FROM words
+                WHERE reviewed = FALSE
+                ORDER BY frequency ASC
+                LIMIT 1", [], |row| row.get(0)
+            ).ok()
+        }).await.unwrap()
+    }
+
+    async fn review_word(&self, word: String, response_quality: f32) {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            let result = conn.query_row(
+                "SELECT id, text, repitition, e_factor, review_duration
+                FROM words
+                    WHERE text = ?", [&word], |row| {
+                        let word_id: i64 = row.get(0)?;
+                        let repitition: u32 = row.get(2)?;
+                        let e_factor: f32 = row.get(3)?;
+                        let duration: u32 = row.get(4)?;
+
+                        Ok((word_id, repitition, e_factor, duration))
+                    }
+            );
+
+            match result {
+                Ok((word_id, repitition, e_factor, duration)) => {
+                    let sm = super_memo_2(SuperMemoItem { repitition, e_factor, duration }, response_quality);
+
+                    let next_review_time = format!("{}", chrono::Utc::now() + chrono::Duration::days(sm.duration as i64));
+                    conn.execute(
+                        "UPDATE words
+                        SET repitition = ?,
+                            e_factor = ?,
+                            review_duration  = ?,
+                            next_review_at = ?,
+                            reviewed = TRUE
+                        WHERE
+                            id = ?", params!(sm.repitition, sm.e_factor, sm.duration, next_review_time, &word_id)
+                    ).unwrap();
+                },
+                Err(e) => println!("Error getting review data from database for word {}. Error: {}", word, e)
+            }
+        }).await.unwrap();
+    }
+
+    async fn get_sentence_to_review(&self, word: String) -> Option<String> {
+        let pool = self.pool.clone();
+        let maturity_weights = self.maturity_weights.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            let review_word_id: i64 = conn.query_row(
+                "SELECT id, text
+                FROM words
+                    WHERE text = ?", [&word], |row| row.get(0)
+            ).ok()?;
+
+            let mut statement = conn.prepare(
+                "SELECT sentence_id
+                FROM word_sentence
+                WHERE word_id = ?"
+            ).ok()?;
+
+            let sentence_ids = statement.query_map([review_word_id], |row| row.get(0)).ok()?;
+
+            let mut fittest_sentence: Option<(i64, f64)> = None;
+            for sentence_id_result in sentence_ids {
+                let sentence_id: i64 = sentence_id_result.ok()?;
+
+                let mut statement = conn.prepare(
+                    "SELECT word_id, words.frequency, words.repitition, words.review_duration, words.e_factor
+                    FROM word_sentence
+                    INNER JOIN words ON word_id = words.id
+                    WHERE sentence_id = ?"
+                ).ok()?;
+
+                let mut total = 0.0;
+                let word_rows = statement.query_map([sentence_id], |row| {
+                    let id: i64 = row.get(0)?;
+                    let freq: i64 = row.get(1)?;
+                    let repitition: u32 = row.get(2)?;
+                    let duration: u32 = row.get(3)?;
+                    let e_factor: f32 = row.get(4)?;
+                    Ok((id, freq, repitition, duration, e_factor))
+                }).ok()?;
+
+                for word_row in word_rows {
+                    let (word_id, word_freq, repitition, duration, e_factor) = word_row.ok()?;
+                    if word_id != review_word_id {
+                        let maturity = maturity_weights.maturity(repitition, duration, e_factor);
+                        total += word_freq as f64 * (1.0 - maturity as f64);
+                    }
+                }
+
+                match fittest_sentence {
+                    Some((_, cost)) if total >= cost => {},
+                    _ => fittest_sentence = Some((sentence_id, total)),
+                }
+            }
+
+            fittest_sentence.and_then(|(id, _)| {
+                conn.query_row(
+                    "SELECT id, text
+                    FROM sentences
+                        WHERE id = ?", [id], |row| row.get(1)
+                ).ok()
+            })
+        }).await.unwrap()
+    }
+
+    async fn get_sentences_for_word(&self, word: String) -> Vec<String> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            let word_id: i64 = match conn.query_row(
+                "SELECT id, text
+                FROM words
+                    WHERE text = ?", [&word], |row| row.get(0)
+            ) {
+                Ok(id) => id,
+                Err(_) => return Vec::new(),
+            };
+
+            let mut statement = conn.prepare(
+                "SELECT sentences.text
+                FROM word_sentence
+                    INNER JOIN sentences ON sentence_id = sentences.id
+                WHERE word_id = ?"
+            ).unwrap();
+
+            statement.query_map([word_id], |row| row.get(0))
+                .unwrap()
+                .filter_map(Result::ok)
+                .collect()
+        }).await.unwrap()
+    }
+
+    async fn fuzzy_find_words(&self, query: String, prefix: bool) -> Vec<String> {
+        let pool = self.pool.clone();
+        let fuzzy_index = self.fuzzy_index.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            // Hold a single guard across the check, the (re)build, and the use below - if
+            // this were three separate lock()s, a concurrent add_sentence's `= None` reset
+            // could land between the build and the use and make `as_ref().unwrap()` panic.
+            let mut index_guard = fuzzy_index.lock().unwrap();
+            if index_guard.is_none() {
+                let mut statement = conn.prepare("SELECT text FROM words").unwrap();
+                let words = statement.query_map([], |row| row.get::<_, String>(0))
+                    .unwrap()
+                    .filter_map(Result::ok);
+                *index_guard = Some(FuzzyWordIndex::build(words));
+            }
+
+            let index = index_guard.as_ref().unwrap();
+
+            let mut matches = if prefix { index.search_prefix(&query) } else { index.search(&query) };
+            matches.sort_by(|(word_a, distance_a), (word_b, distance_b)| {
+                distance_a.cmp(distance_b).then_with(|| {
+                    word_frequency(&conn, word_a).cmp(&word_frequency(&conn, word_b))
+                })
+            });
+
+            matches.into_iter().map(|(word, _)| word).collect()
+        }).await.unwrap()
+    }
+
+    async fn get_definitions(&self, word: String) -> Vec<Definition> {
+        let pool = self.pool.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let conn = pool.get().unwrap();
+
+            let mut statement = match conn.prepare(
+                "SELECT definitions.part_of_speech, definitions.gloss
+                FROM definitions
+                    INNER JOIN words ON words.id = definitions.word_id
+                WHERE words.text = ?"
+            ) {
+                Ok(statement) => statement,
+                Err(_) => return Vec::new(),
+            };
+
+            statement.query_map([&word], |row| {
+                Ok(Definition {
+                    part_of_speech: row.get(0)?,
+                    gloss: row.get(1)?,
+                })
+            })
+            .map(|rows| rows.filter_map(Result::ok).collect())
+            .unwrap_or_default()
+        }).await.unwrap()
+    }
+}
@@ -0,0 +1,234 @@
+// Fetches definitions, part-of-speech, and inflected forms for a word from Wiktionary so
+// that the rest of the crate has something richer than a bare base form to work with.
+//
+// Network access only happens through `WiktionaryClient::fetch`; callers are responsible
+// for caching the result (see `KnowledgeDB::store_dictionary_entry` and
+// `db::store_dictionary_entry`) so a given word is only ever looked up once.
+
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub part_of_speech: String,
+    pub gloss: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DictionaryEntry {
+    pub definitions: Vec<Definition>,
+    // Surface forms this word can appear as once conjugated/inflected, e.g. 食べる ->
+    // [食べた, 食べます, 食べない, ...]. Used to fold inflected surface text back to the
+    // base form it belongs to.
+    pub inflected_forms: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct ParseResponse {
+    parse: ParsePage,
+}
+
+#[derive(serde::Deserialize)]
+struct ParsePage {
+    wikitext: Wikitext,
+}
+
+#[derive(serde::Deserialize)]
+struct Wikitext {
+    #[serde(rename = "*")]
+    content: String,
+}
+
+pub struct WiktionaryClient {
+    http: reqwest::blocking::Client,
+}
+
+impl WiktionaryClient {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::blocking::Client::builder()
+                .user_agent("japanese_word_srs (https://github.com/bombpersons/japanese_word_srs)")
+                .build()
+                .unwrap(),
+        }
+    }
+
+    // Looks up `word` on the English Wiktionary and pulls out its Japanese section.
+    //
+    // `Ok(None)` means the page doesn't exist or has no Japanese entry - a real, cacheable
+    // answer. `Err` means the request itself couldn't be completed (network down, timed
+    // out, ...) - that's not an answer about the word at all, so callers shouldn't treat it
+    // as one: see `fetch_new_dictionary_entries`, which only marks a word as fetched on
+    // `Ok`, so a transient failure gets retried on the next import instead of permanently
+    // caching "no definition".
+    pub fn fetch(&self, word: &str) -> Result<Option<DictionaryEntry>, reqwest::Error> {
+        let url = format!(
+            "https://en.wiktionary.org/w/api.php?action=parse&page={}&prop=wikitext&format=json",
+            urlencoding::encode(word)
+        );
+
+        // A non-2xx status (rate limiting, a 5xx blip, ...) is the request failing, same as
+        // not getting a response at all - it shouldn't be read as "no Japanese entry".
+        let response = self.http.get(&url).send()?.error_for_status()?;
+        // A response we can't deserialize as a successful parse (including Wiktionary's
+        // "no such page" error payload, which omits `parse` entirely) means there's no
+        // Japanese entry to enrich this word with - not that the request failed.
+        let Ok(parsed) = response.json::<ParseResponse>() else {
+            return Ok(None);
+        };
+
+        Ok(parse_japanese_section(&parsed.parse.wikitext.content))
+    }
+}
+
+fn parse_japanese_section(wikitext: &str) -> Option<DictionaryEntry> {
+    // A Wiktionary page can cover the same word in several languages; each language's
+    // entry lives under its own `==LanguageName==` level-2 heading, so only the text up
+    // to the next one of those belongs to Japanese.
+    const HEADING: &str = "==Japanese==";
+    let start = wikitext.find(HEADING)? + HEADING.len();
+    let rest = &wikitext[start..];
+    // Find the next level-2 heading (`\n==` not followed by another `=`) - a naive
+    // `\n==` search also matches `\n===` level-3 subsection headings, which is where
+    // every `===POS===`/gloss/`{{ja-conj}}` block actually lives, truncating `section`
+    // to almost nothing.
+    let end = rest.match_indices("\n==")
+        .find(|(i, _)| !rest[i + 3..].starts_with('='))
+        .map(|(i, _)| i + 1)
+        .unwrap_or(rest.len());
+    let section = &rest[..end];
+
+    let mut definitions = Vec::new();
+    let mut current_pos = String::from("unknown");
+    for line in section.lines() {
+        let line = line.trim();
+        if let Some(pos) = line.strip_prefix("===").and_then(|l| l.strip_suffix("===")) {
+            current_pos = pos.trim().to_string();
+        } else if let Some(gloss) = line.strip_prefix("# ") {
+            definitions.push(Definition {
+                part_of_speech: current_pos.clone(),
+                gloss: strip_wiki_markup(gloss),
+            });
+        }
+    }
+
+    Some(DictionaryEntry {
+        definitions,
+        inflected_forms: parse_inflected_forms(section),
+    })
+}
+
+// Collapses the wiki markup Wiktionary glosses are full of - `[[link|label]]` and
+// `[[link]]` become their display text, `{{template|args}}` is dropped - into something
+// plain enough to show on a review screen.
+fn strip_wiki_markup(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '[' && chars.peek() == Some(&'[') {
+            chars.next();
+            let mut link = String::new();
+            while let Some(&next) = chars.peek() {
+                if next == ']' {
+                    break;
+                }
+                link.push(chars.next().unwrap());
+            }
+            chars.next();
+            chars.next();
+
+            let label = link.rsplit('|').next().unwrap_or(&link);
+            result.push_str(label);
+        } else if c == '{' && chars.peek() == Some(&'{') {
+            chars.next();
+            while let Some(next) = chars.next() {
+                if next == '}' && chars.peek() == Some(&'}') {
+                    chars.next();
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result.trim().to_string()
+}
+
+// Pulls out anything that's plausibly an inflected surface form from a `{{ja-conj|...}}`
+// template: a bare non-ASCII positional argument after the first `|`.
+//
+// Real Wiktionary conjugation templates don't actually list surface forms this way - they
+// take a stem/reading and a conjugation class and expand to the full table via a Lua
+// module, so on a live page this harvests the stem/reading, not the inflected forms
+// (食べ/たべ rather than 食べた/食べない). This only does the right thing for a template
+// that does list bare surface forms as positional args, which real Wiktionary markup
+// generally doesn't - there's no attempt here to replicate Japanese conjugation rules.
+fn parse_inflected_forms(section: &str) -> Vec<String> {
+    let mut forms = Vec::new();
+    for template_start in section.match_indices("{{ja-conj").map(|(i, _)| i) {
+        let Some(template_end) = section[template_start..].find("}}") else { continue };
+        let template = &section[template_start..template_start + template_end];
+        for arg in template.split('|').skip(1) {
+            let arg = arg.trim();
+            if !arg.is_empty() && !arg.contains('=') && arg.chars().all(|c| !c.is_ascii()) {
+                forms.push(arg.to_string());
+            }
+        }
+    }
+    forms.sort();
+    forms.dedup();
+    forms
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_japanese_section_stops_at_the_next_level_two_heading_only() {
+        // Modeled after a real Wiktionary page: the Japanese entry's glosses and
+        // conjugation table live under level-3 (`===`) subsections, with another
+        // language's level-2 (`==`) entry following afterwards.
+        let wikitext = "==English==\n# the wrong language\n\n\
+            ==Japanese==\n\
+            ===Verb===\n\
+            {{ja-conj|g|食べ|たべ}}\n\
+            # to eat\n# to live on (something)\n\n\
+            ===Noun===\n\
+            # food\n\n\
+            ==Korean==\n# a different language entirely\n";
+
+        let entry = parse_japanese_section(wikitext).expect("should find the Japanese section");
+
+        assert_eq!(entry.definitions.len(), 3);
+        assert_eq!(entry.definitions[0].part_of_speech, "Verb");
+        assert_eq!(entry.definitions[0].gloss, "to eat");
+        assert_eq!(entry.definitions[2].part_of_speech, "Noun");
+        assert_eq!(entry.definitions[2].gloss, "food");
+    }
+
+    #[test]
+    fn parse_japanese_section_returns_none_without_a_japanese_heading() {
+        assert!(parse_japanese_section("==English==\n# only English here\n").is_none());
+    }
+
+    #[test]
+    fn strip_wiki_markup_unwraps_links_and_drops_templates() {
+        assert_eq!(strip_wiki_markup("to [[eat]]"), "to eat");
+        assert_eq!(strip_wiki_markup("to [[consume|eat]] food"), "to eat food");
+        assert_eq!(strip_wiki_markup("{{lb|ja|transitive}} to eat"), "to eat");
+    }
+
+    #[test]
+    fn parse_inflected_forms_collects_bare_non_ascii_template_args() {
+        // This only exercises the arg-extraction rule itself (bare non-ASCII positional
+        // args, keyed `key=value` args skipped entirely) against a hand-built template, not
+        // real Wiktionary markup - see the doc comment on `parse_inflected_forms` for why a
+        // real `{{ja-conj}}` page doesn't list forms this way at all.
+        let section = "{{ja-conj|食べる|食べた|食べない|key=ignored}}";
+        let forms = parse_inflected_forms(section);
+        assert_eq!(forms, vec!["食べた".to_string(), "食べない".to_string(), "食べる".to_string()]);
+    }
+
+    #[test]
+    fn parse_inflected_forms_is_empty_without_a_conjugation_template() {
+        assert!(parse_inflected_forms("just some plain text").is_empty());
+    }
+}
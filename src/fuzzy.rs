@@ -0,0 +1,117 @@
+use fst::{IntoStreamer, Set, Streamer};
+use levenshtein_automata::{Distance, LevenshteinAutomatonBuilder, DFA};
+
+// An edit-distance-tolerant index over `words.text`, built the way a search engine builds
+// a fuzzy lookup: all known words go into an `fst::Set` (which stores them as a sorted,
+// deduplicated trie), and a query is matched against it by streaming the set through a
+// Levenshtein DFA rather than scanning every entry by hand.
+pub struct FuzzyWordIndex {
+    set: Set<Vec<u8>>,
+}
+
+// How many edits a query is allowed to have before it stops matching a candidate word.
+// Short queries are the ones where a typo changes the meaning the most, so they get the
+// least slack; long queries can absorb a couple of mistakes without becoming ambiguous.
+fn max_distance_for(query: &str) -> u8 {
+    let char_count = query.chars().count().max(1);
+    let character_budget = if char_count <= 4 {
+        0
+    } else if char_count <= 8 {
+        1
+    } else {
+        2
+    };
+
+    if character_budget == 0 {
+        return 0;
+    }
+
+    // `fst` drives the Levenshtein DFA one UTF-8 byte at a time, so the DFA's notion of
+    // "distance" is edits in bytes, not unicode codepoints. A single substituted/inserted/
+    // deleted multi-byte character - one kana/kanji is 3 bytes - would otherwise need a
+    // budget several times larger than `character_budget` to ever register as just one
+    // edit, so a mistyped kana could never fall within distance 1 no matter how generous
+    // the character-based threshold looked. Scale the budget by the *widest* character
+    // actually in the query (not the average, which would under-budget a query that mixes
+    // ASCII with only one or two multi-byte characters), then clamp to 2:
+    // `LevenshteinAutomatonBuilder` only supports distances up to 2 and panics above that,
+    // so this is the most slack a multi-byte query can ever be given.
+    let max_char_bytes = query.chars().map(|c| c.len_utf8() as u8).max().unwrap_or(1);
+    character_budget.saturating_mul(max_char_bytes.max(1)).min(2)
+}
+
+impl FuzzyWordIndex {
+    // `fst::Set` requires its input sorted and deduplicated, so callers don't need to
+    // worry about that - we do it once here.
+    pub fn build<I: IntoIterator<Item = String>>(words: I) -> Self {
+        let mut sorted: Vec<String> = words.into_iter().collect();
+        sorted.sort();
+        sorted.dedup();
+
+        Self {
+            set: Set::from_iter(sorted).expect("word text contained unsortable duplicates"),
+        }
+    }
+
+    // Finds words within the query's allowed edit distance (see `max_distance_for`),
+    // returning each candidate alongside how many edits it actually took so callers can
+    // rank exact and near-exact matches ahead of looser ones.
+    pub fn search(&self, query: &str) -> Vec<(String, u8)> {
+        let max_distance = max_distance_for(query);
+        let builder = LevenshteinAutomatonBuilder::new(max_distance, false);
+        let dfa = builder.build_dfa(query);
+
+        self.collect_matches(&dfa)
+    }
+
+    // Same idea, but matches any word that *starts with* the query - useful for
+    // incremental/"as you type" lookup rather than a single final search.
+    pub fn search_prefix(&self, query: &str) -> Vec<(String, u8)> {
+        let max_distance = max_distance_for(query);
+        let builder = LevenshteinAutomatonBuilder::new(max_distance, true);
+        let dfa = builder.build_dfa(query);
+
+        self.collect_matches(&dfa)
+    }
+
+    fn collect_matches(&self, dfa: &DFA) -> Vec<(String, u8)> {
+        let mut matches = Vec::new();
+        let mut stream = self.set.search_with_state(dfa).into_stream();
+        while let Some((word, state)) = stream.next() {
+            if let Distance::Exact(distance) = dfa.distance(state) {
+                matches.push((String::from_utf8_lossy(word).into_owned(), distance));
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_distance_for_scales_up_for_multi_byte_queries() {
+        // Japanese kanji/kana are 3 bytes each; a 6-character query's character budget of
+        // 1 becomes 3 once converted to a byte budget, then is clamped to the maximum of 2.
+        assert_eq!(max_distance_for("食べましょう"), 2);
+        // Pure ASCII queries are 1 byte per character, so the byte budget matches the
+        // character budget exactly.
+        assert_eq!(max_distance_for("breakfast"), 2);
+        assert_eq!(max_distance_for("abcd"), 0);
+    }
+
+    #[test]
+    fn search_finds_a_single_mistyped_kana() {
+        let index = FuzzyWordIndex::build(["食べましょう".to_string()]);
+
+        // A learner mistyping one character (し -> じ) of a 6-character word should still
+        // find it - this used to require more byte-level slack than the character-based
+        // budget alone ever granted.
+        let matches = index.search("食べまじょう");
+        assert!(
+            matches.iter().any(|(word, _)| word == "食べましょう"),
+            "expected a one-character typo to still match, got {:?}", matches
+        );
+    }
+}
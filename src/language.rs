@@ -0,0 +1,102 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use lindera::tokenizer::Tokenizer;
+
+use crate::WordFrequencyList;
+
+// Everything the crate needs to know about a language in order to mine sentences and words
+// out of imported text: how to split text into sentences, how to tokenize a sentence into
+// the base forms that get stored as `words`, and how frequent a given word is. The
+// SuperMemo/review code never needs to know which `Language` a word came from - only
+// `add_sentence` and the sentence splitter in `verb_add` touch this trait.
+pub trait Language: Send + Sync {
+    // Short identifier stored in the `sentences.language` / `words.language` columns, e.g.
+    // "ja". Used to select a `Language` back out of a registry.
+    fn code(&self) -> &'static str;
+
+    // Splits `sentence` into the base forms that should be counted as `words` rows. A
+    // token's own surface text is passed through `fold_surface` first; callers use this to
+    // map a known inflected form back to a word it's already seen (see
+    // `KnowledgeDB::fold_surface_form`) before falling back to whatever base form the
+    // tokenizer itself comes up with.
+    fn tokenize_to_base_forms(&self, sentence: &str, fold_surface: &dyn Fn(&str) -> Option<String>) -> Vec<String>;
+
+    // Looks up how frequent `word` is in this language's reference corpus - lower is more
+    // frequent. Unknown words are assumed to be very infrequent.
+    fn word_freq(&self, word: &str) -> i64;
+
+    // Characters that end a sentence.
+    fn sentence_terminators(&self) -> &HashSet<char>;
+
+    // Quote-like character pairs inside which a terminator shouldn't split a sentence,
+    // e.g. a 。 inside a 「」 quotation.
+    fn quote_pairs(&self) -> &[(char, char)];
+}
+
+pub struct Japanese {
+    tokenizer: Tokenizer,
+    word_frequency_list: WordFrequencyList,
+    terminators: HashSet<char>,
+    quote_pairs: Vec<(char, char)>,
+}
+
+impl Japanese {
+    pub fn new() -> Self {
+        Self {
+            tokenizer: Tokenizer::new().unwrap(),
+            word_frequency_list: WordFrequencyList::new(),
+            terminators: HashSet::from(['。', '\n', '！', '？']),
+            quote_pairs: vec![('「', '」')],
+        }
+    }
+}
+
+impl Language for Japanese {
+    fn code(&self) -> &'static str {
+        "ja"
+    }
+
+    fn tokenize_to_base_forms(&self, sentence: &str, fold_surface: &dyn Fn(&str) -> Option<String>) -> Vec<String> {
+        let tokens = self.tokenizer.tokenize(sentence).unwrap();
+        tokens.into_iter()
+            .filter(|token| token.detail.len() > 7)
+            .map(|token| fold_surface(token.text).unwrap_or_else(|| token.detail[6].to_string()))
+            .collect()
+    }
+
+    fn word_freq(&self, word: &str) -> i64 {
+        self.word_frequency_list.get_word_freq(word)
+    }
+
+    fn sentence_terminators(&self) -> &HashSet<char> {
+        &self.terminators
+    }
+
+    fn quote_pairs(&self) -> &[(char, char)] {
+        &self.quote_pairs
+    }
+}
+
+// The set of `Language`s `verb_add` can pick from, keyed by `Language::code`. Adding a new
+// frequency list/tokenizer impl for a new language touches nothing outside this file and
+// its `Language` impl - but registering it here too is NOT yet safe: see the assert below.
+pub fn registry() -> HashMap<String, Arc<dyn Language>> {
+    let mut languages: HashMap<String, Arc<dyn Language>> = HashMap::new();
+    let japanese: Arc<dyn Language> = Arc::new(Japanese::new());
+    languages.insert(japanese.code().to_string(), japanese);
+
+    // `sentences.text`/`words.text` are only unique, and only looked up, per language by
+    // convention, not by constraint or filter (see the migration #3 comment in
+    // migrations.rs) - a second registered language would silently share rows with
+    // Japanese text that happens to collide, corrupting counts and frequencies for both.
+    // Fail loudly here instead of shipping that: widening every `text = ?` lookup (and
+    // `UNIQUE(text)`) to also scope by `language` has to land before a second entry is
+    // added.
+    assert_eq!(
+        languages.len(), 1,
+        "registering a second language isn't safe yet - words/sentences text lookups aren't scoped by language, see migration #3 in migrations.rs"
+    );
+
+    languages
+}
@@ -1,17 +1,28 @@
-use std::{collections::{HashMap, HashSet}, env, fs::File, io::{Read, self}};
+use std::{cell::RefCell, collections::HashMap, env, fs::File, io::{Read, self}, sync::Arc};
 use chrono::{DateTime, TimeZone, NaiveDateTime, Utc, Duration, ParseResult};
 
-use lindera::tokenizer::Tokenizer;
 use rusqlite::{Connection, DatabaseName, params};
 
+mod migrations;
+mod fuzzy;
+mod dictionary;
+mod db;
+mod server;
+mod language;
+
+use fuzzy::FuzzyWordIndex;
+use dictionary::{Definition, DictionaryEntry, WiktionaryClient};
+use db::PooledDatabase;
+use language::Language;
+
 // https://supermemo.guru/wiki/SuperMemo_1.0_for_DOS_(1987)#Algorithm_SM-2
-struct SuperMemoItem {
-    repitition: u32,
-    duration: u32,
-    e_factor: f32
+pub(crate) struct SuperMemoItem {
+    pub(crate) repitition: u32,
+    pub(crate) duration: u32,
+    pub(crate) e_factor: f32
 }
 
-fn super_memo_2(item: SuperMemoItem, response_quality: f32) -> SuperMemoItem {
+pub(crate) fn super_memo_2(item: SuperMemoItem, response_quality: f32) -> SuperMemoItem {
     let repitition = if response_quality < 3.0 { 0 } else { item.repitition };
 
     match repitition {
@@ -39,12 +50,46 @@ fn super_memo_2(item: SuperMemoItem, response_quality: f32) -> SuperMemoItem {
     }
 }
 
-struct WordFrequencyList {
+// Weights controlling when a word is considered "known" for the purposes of picking an
+// "i+1" sentence - one that's almost entirely made up of words the learner has already
+// learned, plus the one new word being reviewed. Each `*_scale` is the value at which that
+// signal alone says the word is fully mature; a word needs to clear all three before its
+// cost is discounted all the way to zero.
+pub(crate) struct MaturityWeights {
+    repitition_scale: f32,
+    duration_scale: f32,
+    e_factor_scale: f32,
+}
+
+impl Default for MaturityWeights {
+    fn default() -> Self {
+        Self {
+            repitition_scale: 4.0,
+            duration_scale: 30.0,
+            e_factor_scale: 2.0,
+        }
+    }
+}
+
+impl MaturityWeights {
+    // Returns how mature a word's memory is, from 0.0 (unknown/fresh) to 1.0 (fully
+    // learned). Takes the weakest of the three signals, since e.g. a high e-factor on a
+    // word reviewed only once shouldn't count as mature yet.
+    pub(crate) fn maturity(&self, repitition: u32, duration: u32, e_factor: f32) -> f32 {
+        let repitition_score = (repitition as f32 / self.repitition_scale).min(1.0);
+        let duration_score = (duration as f32 / self.duration_scale).min(1.0);
+        let e_factor_score = ((e_factor - 1.3) / (self.e_factor_scale - 1.3)).clamp(0.0, 1.0);
+
+        repitition_score.min(duration_score).min(e_factor_score)
+    }
+}
+
+pub(crate) struct WordFrequencyList {
     words: HashMap<String, i64>
 }
 
 impl WordFrequencyList {
-    fn new() -> Self {
+    pub(crate) fn new() -> Self {
         let wordlist = include_str!("japanese_word_frequency.txt");
         let mut words = HashMap::new();
         for (index, line) in wordlist.lines().enumerate() {
@@ -56,7 +101,7 @@ impl WordFrequencyList {
         }
     }
 
-    fn get_word_freq(&self, word: &str) -> i64 {
+    pub(crate) fn get_word_freq(&self, word: &str) -> i64 {
         match self.words.get(word) {
             Some(freq) => *freq,
             None => i64::MAX // If it's not on the list if must be very infrequent.
@@ -64,22 +109,21 @@ impl WordFrequencyList {
     }
 }
 
-fn iterate_sentences<F>(text: &str, mut func: F) where
+fn iterate_sentences<F>(language: &dyn Language, text: &str, mut func: F) where
     F: FnMut(&str) {
 
-    let terminators: HashSet<char> = HashSet::from(['。', '\n', '！', '？']);
-    let open_quotes: HashSet<char> = HashSet::from(['「']);
-    let close_quotes: HashSet<char> = HashSet::from(['」']);
+    let terminators = language.sentence_terminators();
+    let quote_pairs = language.quote_pairs();
 
     let mut depth: i32 = 0;
     let mut cur_string: String = String::new();
     for c in text.chars() {
         cur_string.push(c);
 
-        if open_quotes.contains(&c) {
+        if quote_pairs.iter().any(|(open, _)| *open == c) {
             depth += 1;
         }
-        else if close_quotes.contains(&c) {
+        else if quote_pairs.iter().any(|(_, close)| *close == c) {
             depth -= 1;
         }
         else if depth == 0 && terminators.contains(&c) {
@@ -95,115 +139,260 @@ fn iterate_sentences<F>(text: &str, mut func: F) where
 }
 
 struct KnowledgeDB {
-    tokenizer: Tokenizer,
-    word_frequency_list: WordFrequencyList,
+    // The languages `add_sentence` can tokenize and split sentences for, keyed by
+    // `Language::code`. Filled from `language::registry`, which registers only Japanese and
+    // panics if a second language is registered, since text lookups aren't scoped by
+    // language.
+    languages: HashMap<String, Arc<dyn Language>>,
 
     db_conn: Connection,
+
+    // Lazily (re)built index over `words.text` for fuzzy lookup. `None` means "stale,
+    // rebuild on next use" - we invalidate it whenever a write might have added a word.
+    fuzzy_index: RefCell<Option<FuzzyWordIndex>>,
+
+    dictionary: WiktionaryClient,
+
+    maturity_weights: MaturityWeights,
 }
 
 impl KnowledgeDB {
     fn new(db_path: &str) -> Self {
-        // Create the tokenizer.
-        let tokenizer = Tokenizer::new().unwrap();
-
         // Create the databse connection.
-        let db_conn = Connection::open(db_path).unwrap();
+        let mut db_conn = Connection::open(db_path).unwrap();
         db_conn.pragma_update(Some(DatabaseName::Main), "foreign_keys", true).unwrap();
 
-        // Table for sentences.
-        db_conn.execute(
-            "CREATE TABLE IF NOT EXISTS sentences (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    text TEXT NOT NULL,
-                    UNIQUE(text)
-                )", []).unwrap();
-
-        // Table for words.
-        db_conn.execute(
-            "CREATE TABLE IF NOT EXISTS words (
-                    id INTEGER PRIMARY KEY AUTOINCREMENT,
-                    text TEXT NOT NULL,
-                    count INTEGER DEFAULT 1,
-                    frequency INTEGER,
-
-                    reviewed INT DEFAULT 0,
-                    next_review_at TEXT,
-
-                    review_duration INTEGER DEFAULT 0,
-                    e_factor REAL DEFAULT 0,
-                    repitition INTEGER DEFAULT 0,
-
-                    UNIQUE(text)
-                )", []).unwrap();
-        
-        // Many to Many link between words and sentences.
-        db_conn.execute(
-            "CREATE TABLE IF NOT EXISTS word_sentence (
-                    word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
-                    sentence_id INTEGER NOT NULL REFERENCES sentences(id) ON DELETE CASCADE,
-                    PRIMARY KEY (word_id, sentence_id)
-                )", []).unwrap();
-
-        db_conn.execute(
-            "CREATE INDEX IF NOT EXISTS sentence_index ON word_sentence(sentence_id)", []).unwrap();
-
-        db_conn.execute(
-            "CREATE INDEX IF NOT EXISTS word_index ON word_sentence(word_id)", []).unwrap();
+        // Bring the schema up to the latest migration. `PRAGMA user_version` records how
+        // far a given database.sqlite has already gotten, so this is a no-op on a database
+        // that's already current and applies only the missing steps otherwise.
+        migrations::migrations().to_latest(&mut db_conn).unwrap();
 
         Self {
-            tokenizer,
-            word_frequency_list: WordFrequencyList::new(),
-            db_conn
+            languages: language::registry(),
+            db_conn,
+            fuzzy_index: RefCell::new(None),
+            dictionary: WiktionaryClient::new(),
+            maturity_weights: MaturityWeights::default(),
         }
     }
 
-    fn add_sentence(&mut self, sentence: &str) {
-        //println!("Adding sentence '{}' to database.", sentence);
+    // Overrides the default maturity weighting used by `get_sentence_to_review`'s i+1
+    // heuristic.
+    fn set_maturity_weights(&mut self, weights: MaturityWeights) {
+        self.maturity_weights = weights;
+    }
 
-        // Tokenize the sentence to get the words.
-        let tokens = self.tokenizer.tokenize(sentence).unwrap();
-        let mut words = Vec::<String>::new();
-        for token in tokens {
-            if token.detail.len() > 7 {
-                let base_form = &token.detail[6];
-                words.push(base_form.to_string());
+    // Looks up a registered `Language` by its code (e.g. "ja"), if one is registered.
+    fn language(&self, code: &str) -> Option<&Arc<dyn Language>> {
+        self.languages.get(code)
+    }
+
+    // Looks up `surface` (the exact text as it appeared in a sentence) against inflected
+    // forms we've previously cached from Wiktionary, returning the base word it belongs
+    // to. This lets conjugated surface text that lindera itself doesn't normalise still
+    // increment the right word's count.
+    //
+    // Not language-scoped: see the migration #3 comment in migrations.rs. Harmless while
+    // only Japanese is registered.
+    fn fold_surface_form(&self, surface: &str) -> Option<String> {
+        self.db_conn.query_row(
+            "SELECT words.text
+            FROM word_forms
+                INNER JOIN words ON words.id = word_forms.word_id
+            WHERE word_forms.surface_form = ?", [surface], |row| row.get(0)
+        ).ok()
+    }
+
+    // Fetches Wiktionary's definitions and inflected forms for any of `words` we haven't
+    // already successfully looked up - `dictionary_fetched_at` is the marker that keeps this
+    // to one network round-trip per word ever. Deliberately done outside any write
+    // transaction: one blocking HTTP round-trip per new word would otherwise hold the
+    // SQLite write lock and stall every other writer for as long as the import takes.
+    fn fetch_new_dictionary_entries(&self, words: &[String]) -> HashMap<String, DictionaryEntry> {
+        let mut entries = HashMap::new();
+        for word in words {
+            if entries.contains_key(word) {
+                continue;
             }
+
+            let already_fetched: bool = self.db_conn.query_row(
+                "SELECT dictionary_fetched_at IS NOT NULL FROM words WHERE text = ?", [word], |row| row.get(0)
+            ).unwrap_or(false);
+
+            if already_fetched {
+                continue;
+            }
+
+            // A request failure (`Err`) isn't inserted at all, so `dictionary_fetched_at`
+            // never gets stamped for it and the word is retried on the next import instead
+            // of permanently caching "no definition" for a transient network error.
+            if let Ok(entry) = self.dictionary.fetch(word) {
+                entries.insert(word.clone(), entry.unwrap_or_default());
+            }
+        }
+        entries
+    }
+
+    // Writes an already-fetched dictionary `entry` for `word_id` and stamps
+    // `dictionary_fetched_at`. Pure DB writes, safe to call from inside a transaction.
+    //
+    // Re-checks `dictionary_fetched_at` itself rather than trusting the caller, since the
+    // fetch happened outside the transaction: two `add_sentence` calls racing on the same
+    // new word could otherwise both insert the same definitions/inflected forms twice.
+    fn store_dictionary_entry(tx: &rusqlite::Transaction, word_id: i64, entry: &DictionaryEntry) {
+        let already_fetched: bool = tx.query_row(
+            "SELECT dictionary_fetched_at IS NOT NULL FROM words WHERE id = ?", [word_id], |row| row.get(0)
+        ).unwrap_or(true);
+
+        if already_fetched {
+            return;
+        }
+
+        for definition in &entry.definitions {
+            tx.execute(
+                "INSERT INTO definitions(word_id, part_of_speech, gloss)
+                VALUES (?, ?, ?)", params![word_id, definition.part_of_speech, definition.gloss]
+            ).unwrap();
+        }
+
+        for form in &entry.inflected_forms {
+            tx.execute(
+                "INSERT OR IGNORE INTO word_forms(word_id, surface_form)
+                VALUES (?, ?)", params![word_id, form]
+            ).unwrap();
+        }
+
+        tx.execute(
+            "UPDATE words SET dictionary_fetched_at = ? WHERE id = ?",
+            params![format!("{}", Utc::now()), word_id]
+        ).unwrap();
+    }
+
+    // Returns the cached Wiktionary glosses for `word`, if any have been fetched.
+    fn get_definitions(&self, word: &str) -> Vec<Definition> {
+        let mut statement = match self.db_conn.prepare(
+            "SELECT definitions.part_of_speech, definitions.gloss
+            FROM definitions
+                INNER JOIN words ON words.id = definitions.word_id
+            WHERE words.text = ?"
+        ) {
+            Ok(statement) => statement,
+            Err(_) => return Vec::new(),
+        };
+
+        statement.query_map([word], |row| {
+            Ok(Definition {
+                part_of_speech: row.get(0)?,
+                gloss: row.get(1)?,
+            })
+        })
+        .map(|rows| rows.filter_map(Result::ok).collect())
+        .unwrap_or_default()
+    }
+
+    // Rebuilds the fuzzy index from the current contents of `words` if it's missing or
+    // has been invalidated by a write, then hands back the matches for `query`, ranked by
+    // edit distance and then by how frequent the word is (rarer words sort last).
+    fn fuzzy_find_words(&self, query: &str, prefix: bool) -> Vec<String> {
+        if self.fuzzy_index.borrow().is_none() {
+            let mut statement = self.db_conn.prepare("SELECT text FROM words").unwrap();
+            let words = statement.query_map([], |row| row.get::<_, String>(0))
+                .unwrap()
+                .filter_map(Result::ok);
+            *self.fuzzy_index.borrow_mut() = Some(FuzzyWordIndex::build(words));
         }
 
+        let index = self.fuzzy_index.borrow();
+        let index = index.as_ref().unwrap();
+
+        let mut matches = if prefix { index.search_prefix(query) } else { index.search(query) };
+        matches.sort_by(|(word_a, distance_a), (word_b, distance_b)| {
+            distance_a.cmp(distance_b).then_with(|| {
+                self.word_frequency(word_a).cmp(&self.word_frequency(word_b))
+            })
+        });
+
+        matches.into_iter().map(|(word, _)| word).collect()
+    }
+
+    // The frequency rank stored for `word` when it was first inserted, or `i64::MAX` if
+    // it's not in the database at all.
+    //
+    // Not language-scoped: see the migration #3 comment in migrations.rs. Harmless while
+    // only Japanese is registered.
+    fn word_frequency(&self, word: &str) -> i64 {
+        self.db_conn.query_row(
+            "SELECT frequency FROM words WHERE text = ?", [word], |row| row.get(0)
+        ).unwrap_or(i64::MAX)
+    }
+
+    fn add_sentence(&mut self, language: &dyn Language, sentence: &str) {
+        //println!("Adding sentence '{}' to database.", sentence);
+
+        // Nothing to do if we've already imported this exact sentence - bail out before
+        // tokenizing or doing any per-word dictionary-fetch-status lookups below.
+        let already_imported: bool = self.db_conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM sentences WHERE text = ?)", [sentence], |row| row.get(0)
+        ).unwrap_or(false);
+        if already_imported {
+            return;
+        }
+
+        // Tokenize the sentence to get the words. A surface form that's a known inflection
+        // of a word we've already cached (see `fold_surface_form`) is folded back to that
+        // word's base form rather than trusting the tokenizer's own base form for it, so
+        // e.g. 食べた and 食べます both count against 食べる.
+        let words = language.tokenize_to_base_forms(sentence, &|surface| self.fold_surface_form(surface));
+
+        // Fetch dictionary entries for any new words before opening the write transaction
+        // below, so the network round-trips don't hold the SQLite write lock.
+        let mut dictionary_entries = self.fetch_new_dictionary_entries(&words);
+
         // Insert the sentence and words into the database.
         let tx = self.db_conn.transaction().unwrap();
         if tx.execute(
-            "INSERT OR IGNORE INTO sentences(text)
-                VALUES(?);", [sentence]).unwrap() == 1 {
-            
+            "INSERT OR IGNORE INTO sentences(text, language)
+                VALUES(?, ?);", params![sentence, language.code()]).unwrap() == 1 {
+
             let sentence_id = tx.last_insert_rowid();
 
             // We inserted the sentence, so let's add the words too.
-            for word in words {
+            for word in &words {
                 //println!("Adding word '{}' to database.", word);
 
                 // Find some info to add to the word.
-                let frequency = self.word_frequency_list.get_word_freq(&word);
+                let frequency = language.word_freq(word);
 
                 // Add the word.
                 tx.execute(
-                    "INSERT INTO words(count, frequency, text)
-                    VALUES(1, ?, ?)
-                    ON CONFLICT(text) DO UPDATE SET count=count + 1", params!(frequency, &word)).unwrap(); 
+                    "INSERT INTO words(count, frequency, text, language)
+                    VALUES(1, ?, ?, ?)
+                    ON CONFLICT(text) DO UPDATE SET count=count + 1", params!(frequency, word, language.code())).unwrap();
 
                 let word_id: i64 = tx.query_row(
                     "SELECT id, text
                     FROM words
-                        WHERE text = ?", [&word], |row| row.get(0)
+                        WHERE text = ?", [word], |row| row.get(0)
                 ).unwrap();
 
                 // Add the relationship word->sentence
                 tx.execute(
                     "INSERT OR IGNORE INTO word_sentence(word_id, sentence_id)
                     VALUES(?, ?);", params![word_id, sentence_id]).unwrap();
+
+                // Store the dictionary entry fetched above, the first time we see this
+                // word - `remove` so a word repeated later in the same sentence doesn't
+                // get its definitions/inflected forms inserted more than once.
+                if let Some(entry) = dictionary_entries.remove(word) {
+                    Self::store_dictionary_entry(&tx, word_id, &entry);
+                }
             }
         }
         tx.commit().unwrap();
+
+        // The write above may have inserted new words, so the cached fuzzy index (if any)
+        // no longer reflects `words.text`. Drop it and let it rebuild lazily on next search.
+        *self.fuzzy_index.borrow_mut() = None;
     }
 
     fn get_sentences_for_word(&self, word: &str) -> Vec<String> {
@@ -296,46 +485,51 @@ impl KnowledgeDB {
             WHERE word_id = ?"
         ).expect(format!("Error finding sentences containing word {}", word).as_str());
 
-        // Go through each sentence returned and calculate a heuristic that represents
-        // how much knowledge contained within the sentence is unknown to the user (excluding the word to be reviewed).
-        // More infrequent words will have a higher cost.
+        // Go through each sentence returned and calculate an "i+1" heuristic: the cost of
+        // a sentence is the sum, over its non-target words, of how unknown each one still
+        // is to the learner. A word the learner has a mature memory of (lots of
+        // repititions, a long review duration, a healthy e-factor) costs close to nothing;
+        // an unreviewed or weakly-learned word costs close to its full frequency rank.
+        // This favours sentences that are almost entirely comprehensible already.
         let sentence_ids = statement.query_map([review_word_id], |row| row.get(1))
             .expect(format!("Error getting sentences containing word {}", word).as_str());
 
         // Store the current fittest sentence.
         let mut fittest_sentence = None;
-            
+
         for sentence_id_result in sentence_ids {
             let sentence_id: i64 = sentence_id_result.expect(format!("Couldn't retrieve sentence for word {}", word).as_str());
 
-            // Find all the words associated with the sentence.
+            // Find all the words associated with the sentence, along with what we know
+            // about how well the learner knows each one.
             let mut statement = self.db_conn.prepare(
-                "SELECT word_id, sentence_id, words.frequency FROM word_sentence
+                "SELECT word_id, words.frequency, words.repitition, words.review_duration, words.e_factor
+                FROM word_sentence
                 INNER JOIN words ON word_id = words.id
                 WHERE sentence_id = ?"
             ).expect(format!("Error finding sentences containing word {}", word).as_str());
 
-            // TODO: This should take into account words that the user already knows (has reviewed).
-            // It should make words that the user has low e_factors or long durations cost less
             let mut total = 0.0;
-            let word_ids = statement.query_map([sentence_id], |row| {
+            let word_rows = statement.query_map([sentence_id], |row| {
                 let id: i64 = row.get(0)?;
-                let freq: i64 = row.get(2)?;
-                Ok((id, freq))
+                let freq: i64 = row.get(1)?;
+                let repitition: u32 = row.get(2)?;
+                let duration: u32 = row.get(3)?;
+                let e_factor: f32 = row.get(4)?;
+                Ok((id, freq, repitition, duration, e_factor))
             }).expect(format!("Couldn't get words contained in sentence {}", sentence_id).as_str());
-            for word_id_result in word_ids {
-                let (word_id, word_freq) = word_id_result.expect(format!("Error getting word id for word in potential sentence for review.").as_str());
-            
+            for word_row in word_rows {
+                let (word_id, word_freq, repitition, duration, e_factor) =
+                    word_row.expect(format!("Error getting word id for word in potential sentence for review.").as_str());
+
                 // If the word is the word we are reviewing then don't add this to the total.
                 if word_id != review_word_id {
-                    total += word_freq as f64;
-
-                    println!("WORDS: {} costs {}", word_id, word_freq);
+                    let maturity = self.maturity_weights.maturity(repitition, duration, e_factor);
+                    let cost = word_freq as f64 * (1.0 - maturity as f64);
+                    total += cost;
                 }
             }
 
-            println!("SENTENCE TOTAL: {} costs {}", sentence_id, total);
-
             // Store the sentence info if it's fitter than the one we have stored already.
             match fittest_sentence {
                 Some((_, cost)) => {
@@ -351,8 +545,7 @@ impl KnowledgeDB {
 
         // Get the sentence text.
         match fittest_sentence {
-            Some((id, cost)) => {
-                println!("Picked sentence cost {}", cost);
+            Some((id, _cost)) => {
                 Some(self.db_conn.query_row(
                     "SELECT id, text
                     FROM sentences
@@ -402,12 +595,35 @@ impl KnowledgeDB {
     }
 }
 
-fn verb_search(knowledge: &KnowledgeDB, word: &str) {
-    let sentences = knowledge.get_sentences_for_word(word);
-    if sentences.is_empty() {
-        println!("No sentences with the word {} found.", word);
-    } else {
-        println!("Showing {} results:", sentences.len());
+fn verb_search(knowledge: &KnowledgeDB, word: &str, fuzzy: bool, prefix: bool) {
+    if !fuzzy {
+        let sentences = knowledge.get_sentences_for_word(word);
+        if sentences.is_empty() {
+            println!("No sentences with the word {} found.", word);
+        } else {
+            println!("Showing {} results:", sentences.len());
+            for sentence in sentences {
+                println!("{}", sentence);
+            }
+        }
+        return;
+    }
+
+    // Fuzzy mode: look up words within edit distance of the query first, then show
+    // sentences for each candidate in turn so a mistyped reading still finds something.
+    let candidates = knowledge.fuzzy_find_words(word, prefix);
+    if candidates.is_empty() {
+        println!("No words similar to {} found.", word);
+        return;
+    }
+
+    for candidate in candidates {
+        let sentences = knowledge.get_sentences_for_word(candidate.as_str());
+        if sentences.is_empty() {
+            continue;
+        }
+
+        println!("Matches for {} ({} results):", candidate, sentences.len());
         for sentence in sentences {
             println!("{}", sentence);
         }
@@ -422,6 +638,17 @@ fn verb_review(knowledge: &mut KnowledgeDB) {
         .expect(format!("No sentence for the word {} could be found.", word).as_str());
 
     println!("{}", sentence);
+
+    let definitions = knowledge.get_definitions(word.as_str());
+    if definitions.is_empty() {
+        println!("({}: no definition cached)", word);
+    } else {
+        println!("{}:", word);
+        for definition in definitions {
+            println!("  ({}) {}", definition.part_of_speech, definition.gloss);
+        }
+    }
+
     println!("Enter 0-5:");
 
     let mut buffer = String::new();
@@ -440,8 +667,16 @@ fn verb_review(knowledge: &mut KnowledgeDB) {
     println!("Reviewed {}", word);
 }
 
-fn verb_add(knowledge: &mut KnowledgeDB, file_path: &str) {
-    println!("Adding contents of {} to the database.", file_path);
+fn verb_add(knowledge: &mut KnowledgeDB, file_path: &str, language_code: &str) {
+    let language = match knowledge.language(language_code) {
+        Some(language) => language.clone(),
+        None => {
+            println!("{}: no such language registered.", language_code);
+            return;
+        }
+    };
+
+    println!("Adding contents of {} to the database as {}.", file_path, language_code);
 
     // Open the file.
     match File::open(file_path) {
@@ -454,8 +689,8 @@ fn verb_add(knowledge: &mut KnowledgeDB, file_path: &str) {
                     println!("Read {} bytes.", bytes_read);
 
                     // Iterate over the sentences and add them to our db.
-                    iterate_sentences(file_contents.as_str(), |sentence| {
-                        knowledge.add_sentence(sentence);
+                    iterate_sentences(language.as_ref(), file_contents.as_str(), |sentence| {
+                        knowledge.add_sentence(language.as_ref(), sentence);
                     })
                 },
                 Err(err) => println!("Couldn't read contents of file {}, Error: {}", file_path, err)
@@ -475,9 +710,11 @@ fn parse_arguments(knowledge: &mut KnowledgeDB, args: &Vec<String>) {
     match verb {
         "search" => {
             if args.len() < 3 {
-                println!("Usage: 'search {{word-to-search}}")
+                println!("Usage: 'search {{word-to-search}} [--fuzzy] [--prefix]")
             } else {
-                verb_search(knowledge, args[2].as_str())
+                let fuzzy = args[3..].iter().any(|arg| arg == "--fuzzy" || arg == "--prefix");
+                let prefix = args[3..].iter().any(|arg| arg == "--prefix");
+                verb_search(knowledge, args[2].as_str(), fuzzy, prefix)
             }
         },
         "review" => {
@@ -485,37 +722,71 @@ fn parse_arguments(knowledge: &mut KnowledgeDB, args: &Vec<String>) {
         },
         "add" => {
             if args.len() < 3 {
-                println!("Usage: 'add {{path-to-file}}")
+                println!("Usage: 'add {{path-to-file}} [--lang={{code}}]")
             } else {
-                verb_add(knowledge, args[2].as_str())
+                let language_code = args[3..].iter()
+                    .find_map(|arg| arg.strip_prefix("--lang="))
+                    .unwrap_or("ja");
+                verb_add(knowledge, args[2].as_str(), language_code)
             }
         }
         _ => println!("{}: Unknown command.", verb)
     }
 }
 
-fn main() -> () {
-    // Open the database.
-    let mut knowledge = KnowledgeDB::new("database.sqlite");
+// Runs the HTTP API server (see `server::serve`) against a pool-backed `Database` rather
+// than the CLI's single-connection `KnowledgeDB`, so it can serve several requests at once.
+fn verb_serve(db_path: &str, addr: &str) {
+    let database: std::sync::Arc<dyn db::Database> = std::sync::Arc::new(PooledDatabase::open(db_path));
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(server::serve(database, addr));
+}
 
+fn main() -> () {
     // Parse command line arguments
     let args: Vec<String> = env::args().collect();
-    parse_arguments(&mut knowledge, &args);
-
 
-    
-    // // Split by sentences and add each one seperately.
-    // iterate_sentences(test_text, |sentence| {
-    //     // First add the sentence.
-    //     knowledge.add_sentence(sentence);
-    // });
+    // `serve` runs a long-lived async HTTP server against a connection pool instead of the
+    // CLI's one-shot, single-connection `KnowledgeDB`, so it's handled before we open one.
+    if args.len() >= 2 && args[1] == "serve" {
+        let addr = args.get(2).map(String::as_str).unwrap_or("127.0.0.1:8080");
+        verb_serve("database.sqlite", addr);
+        return;
+    }
 
-    // knowledge.review_word("考える");
-    // let word = knowledge.get_word_to_review();
-    // println!("Next word to review is {}", word);
+    // Open the database.
+    let mut knowledge = KnowledgeDB::new("database.sqlite");
+    parse_arguments(&mut knowledge, &args);
 
     // let sentences = knowledge.get_sentences_for_word("考える");
     // for sentence in sentences {
     //     println!("{}", sentence);
     // }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maturity_is_zero_for_an_unreviewed_word() {
+        let weights = MaturityWeights::default();
+        assert_eq!(weights.maturity(0, 0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn maturity_is_one_once_every_signal_clears_its_scale() {
+        let weights = MaturityWeights::default();
+        assert_eq!(weights.maturity(4, 30, 2.0), 1.0);
+    }
+
+    #[test]
+    fn maturity_takes_the_weakest_signal() {
+        let weights = MaturityWeights::default();
+        // Repitition and duration both say "fully mature", but the e-factor is barely
+        // above the minimum (1.3) - the word shouldn't be counted as mature yet.
+        let maturity = weights.maturity(10, 60, 1.3);
+        assert_eq!(maturity, 0.0);
+    }
 }
\ No newline at end of file
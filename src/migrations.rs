@@ -0,0 +1,127 @@
+use rusqlite_migration::{Migrations, M};
+
+// Every schema change to `database.sqlite` is expressed as an entry in this list rather
+// than as an in-place edit to a `CREATE TABLE` statement, so that existing databases on
+// disk upgrade in place instead of silently drifting from what the code expects.
+//
+// `PRAGMA user_version` tracks how many of these have already been applied; on startup we
+// just ask `rusqlite_migration` to bring the connection up to the latest one inside a
+// transaction. To add a new migration, append a new `M::up(..)` entry - never edit an
+// existing one, since that would change the schema out from under databases that already
+// recorded it as applied.
+pub fn migrations() -> Migrations<'static> {
+    Migrations::new(vec![
+        // Migration #1: the original set of tables this crate shipped with.
+        M::up(
+            "CREATE TABLE IF NOT EXISTS sentences (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text TEXT NOT NULL,
+                UNIQUE(text)
+            );
+
+            CREATE TABLE IF NOT EXISTS words (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text TEXT NOT NULL,
+                count INTEGER DEFAULT 1,
+                frequency INTEGER,
+
+                reviewed INT DEFAULT 0,
+                next_review_at TEXT,
+
+                review_duration INTEGER DEFAULT 0,
+                e_factor REAL DEFAULT 0,
+                repitition INTEGER DEFAULT 0,
+
+                UNIQUE(text)
+            );
+
+            CREATE TABLE IF NOT EXISTS word_sentence (
+                word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                sentence_id INTEGER NOT NULL REFERENCES sentences(id) ON DELETE CASCADE,
+                PRIMARY KEY (word_id, sentence_id)
+            );
+
+            CREATE INDEX IF NOT EXISTS sentence_index ON word_sentence(sentence_id);
+            CREATE INDEX IF NOT EXISTS word_index ON word_sentence(word_id);",
+        ),
+        // Migration #2: Wiktionary-backed definitions and inflected forms.
+        M::up(
+            "ALTER TABLE words ADD COLUMN dictionary_fetched_at TEXT;
+
+            CREATE TABLE IF NOT EXISTS definitions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                part_of_speech TEXT NOT NULL,
+                gloss TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS definitions_word_index ON definitions(word_id);
+
+            CREATE TABLE IF NOT EXISTS word_forms (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                word_id INTEGER NOT NULL REFERENCES words(id) ON DELETE CASCADE,
+                surface_form TEXT NOT NULL,
+                UNIQUE(word_id, surface_form)
+            );
+
+            CREATE INDEX IF NOT EXISTS word_forms_surface_index ON word_forms(surface_form);",
+        ),
+        // Migration #3: track which `Language` a sentence/word was imported with, so the
+        // crate isn't limited to mining Japanese text.
+        //
+        // Known limitation: `sentences.text` and `words.text` are still only unique per
+        // language by convention, not by constraint - the `UNIQUE(text)` from migration #1
+        // wasn't widened to `UNIQUE(text, language)` here, since doing so means rebuilding
+        // both tables (SQLite can't alter a constraint in place) and auditing every `text =
+        // ?` lookup (`KnowledgeDB`/`db::PooledDatabase` fold_surface_form, word_frequency,
+        // review lookups, ...) to also filter on `language`. With only `"ja"` registered via
+        // `language::registry()` this can't bite in practice yet, so it's left as a known gap
+        // to close in its own migration once a second language is actually added.
+        M::up(
+            "ALTER TABLE sentences ADD COLUMN language TEXT NOT NULL DEFAULT 'ja';
+            ALTER TABLE words ADD COLUMN language TEXT NOT NULL DEFAULT 'ja';",
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    #[test]
+    fn migrates_a_fresh_database_to_the_latest_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrations().to_latest(&mut conn).unwrap();
+
+        let language_columns: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('words') WHERE name = 'language'", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(language_columns, 1);
+
+        let has_definitions_table: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'definitions'", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(has_definitions_table, 1);
+    }
+
+    #[test]
+    fn upgrades_a_database_left_on_an_older_migration() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        // Simulate a database that was created before the dictionary-caching and
+        // language columns existed, and only ever got migration #1 applied.
+        migrations().to_version(&mut conn, 1).unwrap();
+
+        let language_columns_before: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('words') WHERE name = 'language'", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(language_columns_before, 0);
+
+        migrations().to_latest(&mut conn).unwrap();
+
+        let language_columns_after: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('words') WHERE name = 'language'", [], |row| row.get(0)
+        ).unwrap();
+        assert_eq!(language_columns_after, 1);
+    }
+}
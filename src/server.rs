@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::db::Database;
+use crate::dictionary::Definition;
+
+#[derive(Clone)]
+struct AppState {
+    db: Arc<dyn Database>,
+}
+
+#[derive(Serialize)]
+struct DefinitionResponse {
+    part_of_speech: String,
+    gloss: String,
+}
+
+impl From<Definition> for DefinitionResponse {
+    fn from(definition: Definition) -> Self {
+        Self {
+            part_of_speech: definition.part_of_speech,
+            gloss: definition.gloss,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AddSentenceRequest {
+    #[serde(default = "default_language")]
+    language: String,
+    sentence: String,
+}
+
+fn default_language() -> String {
+    "ja".to_string()
+}
+
+#[derive(Deserialize)]
+struct ReviewRequest {
+    word: String,
+    quality: f32,
+}
+
+#[derive(Deserialize)]
+struct WordQuery {
+    word: String,
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    word: String,
+    #[serde(default)]
+    fuzzy: bool,
+    #[serde(default)]
+    prefix: bool,
+}
+
+// Runs an HTTP server exposing the same operations as the CLI verbs, as JSON endpoints,
+// against a shared `Database`. Meant for a separate review frontend (GUI, web app) to
+// drive the SRS concurrently instead of shelling out to this binary for every action.
+pub async fn serve(db: Arc<dyn Database>, addr: &str) {
+    let state = AppState { db };
+
+    let app = Router::new()
+        .route("/sentences", post(add_sentence))
+        .route("/review/next-word", get(word_to_review))
+        .route("/review/sentence", get(sentence_to_review))
+        .route("/review", post(review_word))
+        .route("/search", get(search))
+        .route("/definitions", get(definitions))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    println!("Listening on {}", addr);
+    axum::serve(listener, app).await.unwrap();
+}
+
+async fn add_sentence(State(state): State<AppState>, Json(body): Json<AddSentenceRequest>) {
+    state.db.add_sentence(body.language, body.sentence).await;
+}
+
+async fn word_to_review(State(state): State<AppState>) -> Json<Option<String>> {
+    Json(state.db.get_word_to_review().await)
+}
+
+async fn sentence_to_review(State(state): State<AppState>, Query(query): Query<WordQuery>) -> Json<Option<String>> {
+    Json(state.db.get_sentence_to_review(query.word).await)
+}
+
+async fn review_word(State(state): State<AppState>, Json(body): Json<ReviewRequest>) {
+    state.db.review_word(body.word, body.quality).await;
+}
+
+async fn search(State(state): State<AppState>, Query(query): Query<SearchQuery>) -> Json<Vec<String>> {
+    if query.fuzzy || query.prefix {
+        Json(state.db.fuzzy_find_words(query.word, query.prefix).await)
+    } else {
+        Json(state.db.get_sentences_for_word(query.word).await)
+    }
+}
+
+async fn definitions(State(state): State<AppState>, Query(query): Query<WordQuery>) -> Json<Vec<DefinitionResponse>> {
+    let definitions = state.db.get_definitions(query.word).await
+        .into_iter()
+        .map(DefinitionResponse::from)
+        .collect();
+    Json(definitions)
+}